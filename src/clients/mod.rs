@@ -0,0 +1,24 @@
+//! Provider-specific API client implementations.
+
+pub mod qwen;
+mod sse;
+
+use crate::error::{ApiError, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+
+/// Converts user-supplied header name/value pairs into a [`HeaderMap`],
+/// shared by every provider client's `build_headers`.
+pub(crate) fn build_headers(custom: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (key, value) in custom {
+        let name = HeaderName::from_bytes(key.as_bytes()).map_err(|e| ApiError::Internal {
+            message: format!("Invalid header name {}: {}", key, e),
+        })?;
+        let value = HeaderValue::from_str(value).map_err(|e| ApiError::Internal {
+            message: format!("Invalid header value for {}: {}", key, e),
+        })?;
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
@@ -16,16 +16,17 @@
 //! - [`QwenResponse`]: Represents the structured response from the API
 //! - [`StreamEvent`]: Represents different types of events in streaming responses
 
+use super::sse::SseDecoder;
 use crate::{
     error::{ApiError, Result},
-    models::{ApiConfig, Message, Role},
+    models::{ApiConfig, Message, MessageContent, Role, ToolCall},
 };
 use futures::Stream;
 use futures::StreamExt;
 use reqwest::{header::HeaderMap, Client};
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::{collections::HashMap, pin::Pin};
+use std::{collections::HashMap, pin::Pin, time::Duration};
 
 pub(crate) const QWEN_API_URL: &str =
     "https://dashscope.aliyuncs.com/compatible-mode/v1/chat/completions";
@@ -36,6 +37,38 @@ const DEFAULT_MODEL: &str = "qwen-plus";
 pub struct QwenClient {
     pub(crate) client: Client,
     api_token: String,
+    retry: RetryPolicy,
+}
+
+/// Transport and retry configuration for [`QwenClient::with_config`].
+#[derive(Debug, Clone, Default)]
+pub struct QwenClientConfig {
+    /// Per-request timeout; unset means reqwest's default (no timeout).
+    pub timeout: Option<Duration>,
+    /// An HTTP/HTTPS proxy URL applied to every request.
+    pub proxy: Option<String>,
+    /// Retry behavior for transient failures.
+    pub retry: RetryPolicy,
+}
+
+/// A bounded exponential-backoff retry policy applied to transient
+/// failures (connection errors, timeouts, `429`, `5xx`).
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first; `1` disables retries.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -67,33 +100,39 @@ pub(crate) struct QwenRequest {
     messages: Vec<QwenMessage>,
     stream: bool,
 
+    /// Tool/function definitions the model may call; `tool_choice` is
+    /// passed through unchanged via `additional_params`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<serde_json::Value>>,
+
     #[serde(flatten)]
     additional_params: serde_json::Value,
 }
 
 // Event types for streaming responses
-#[derive(Debug, Deserialize)]
-#[serde(tag = "data")]
+/// A single event decoded from the `chat_stream` SSE response.
+#[derive(Debug)]
 #[allow(unused)]
 pub enum StreamEvent {
-    #[serde(rename = "data")]
-    Message {
-        id: String,
-        object: String,
-        created: i64,
-        model: String,
-        choices: Vec<StreamChoice>,
-        usage: Option<Usage>,
-        // service_tier: Option<String>,
-        system_fingerprint: Option<String>,
-    },
-    #[serde(rename = "NONE")]
-    None,
-    // #[serde(rename = "error")]
-    // Error { error: StreamError },
+    /// One incremental chat-completion chunk.
+    Message(QwenStreamChunk),
+    /// The literal `[DONE]` sentinel marking the end of the stream.
+    Done,
+}
+
+/// The JSON payload of a single `data:` frame for a non-`[DONE]` event.
+#[derive(Debug, Deserialize, Clone)]
+pub struct QwenStreamChunk {
+    pub id: String,
+    pub object: String,
+    pub created: i64,
+    pub model: String,
+    pub choices: Vec<StreamChoice>,
+    pub usage: Option<Usage>,
+    pub system_fingerprint: Option<String>,
 }
 #[allow(unused)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct StreamChoice {
     pub index: i32,
     pub delta: QwenMessage,
@@ -101,16 +140,152 @@ pub struct StreamChoice {
     pub logprobs: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub(crate) struct QwenMessage {
     pub role: Option<String>,
-    pub content: Option<String>,
+    /// Plain text for text-only models, or an array of typed parts
+    /// (`text` / `image_url`) for vision-capable models such as
+    /// `qwen-vl-plus` and `qwen-vl-max`.
+    pub content: Option<MessageContent>,
+    /// Tool calls requested by the model. Streamed deltas carry partial
+    /// fragments keyed by `index`; see [`QwenClient::chat_stream`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// Present on `role: "tool"` messages sent back to the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
-#[allow(unused)]
-#[derive(Debug, Deserialize)]
+/// A DashScope error event surfaced mid-stream (rate limiting, content
+/// filtering, quota, ...), either as a top-level `{code, message}` frame or
+/// a nested `{"error": {code, message}}` frame.
+#[derive(Debug, Clone)]
 pub struct StreamError {
+    pub code: Option<String>,
     pub message: String,
-    pub code: String,
+    pub request_id: Option<String>,
+}
+
+/// Attempts to read `value` as a DashScope stream error frame, checking a
+/// nested `error` object first and falling back to top-level fields.
+fn parse_stream_error(value: &serde_json::Value) -> Option<StreamError> {
+    let error_obj = value.get("error").unwrap_or(value);
+    let message = error_obj.get("message")?.as_str()?.to_string();
+    let code = error_obj.get("code").and_then(|c| {
+        c.as_str()
+            .map(str::to_string)
+            .or_else(|| c.as_i64().map(|n| n.to_string()))
+    });
+    let request_id = value
+        .get("request_id")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+
+    Some(StreamError {
+        code,
+        message,
+        request_id,
+    })
+}
+
+/// Parses a `Retry-After` header value (seconds, per RFC 9110) into a
+/// [`Duration`], if present and numeric.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// Doubles `current`, capped at `max`.
+fn next_backoff(current: Duration, max: Duration) -> Duration {
+    (current * 2).min(max)
+}
+
+/// Sends the chat completion request, retrying transient failures
+/// (connection errors, timeouts, `429`, `5xx`) with exponential backoff,
+/// honoring a `Retry-After` header when present. A free function rather
+/// than a `QwenClient` method so `chat_stream`'s `async_stream` generator
+/// can call it without holding a borrow of `&self` across an await point.
+async fn send_with_retry(
+    client: &Client,
+    retry: &RetryPolicy,
+    headers: &HeaderMap,
+    request: &QwenRequest,
+) -> Result<reqwest::Response> {
+    let mut attempt = 0u32;
+    let mut backoff = retry.initial_backoff;
+
+    loop {
+        attempt += 1;
+        match client
+            .post(QWEN_API_URL)
+            .headers(headers.clone())
+            .json(request)
+            .send()
+            .await
+        {
+            Ok(response) => {
+                let status = response.status();
+                let transient =
+                    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                if !transient || attempt >= retry.max_attempts {
+                    return Ok(response);
+                }
+                let wait = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_retry_after)
+                    .unwrap_or(backoff);
+                tokio::time::sleep(wait).await;
+            }
+            Err(e) => {
+                let err = ApiError::QwenError {
+                    message: format!("Request failed: {}", e),
+                    type_: "request_failed".to_string(),
+                    param: None,
+                    code: None,
+                    request_id: None,
+                };
+                if attempt >= retry.max_attempts || !(e.is_connect() || e.is_timeout()) {
+                    return Err(err);
+                }
+                tokio::time::sleep(backoff).await;
+            }
+        }
+        backoff = next_backoff(backoff, retry.max_backoff);
+    }
+}
+
+/// Folds streamed tool-call deltas into complete [`ToolCall`]s. Keyed by
+/// `(choice_index, delta_index)` rather than `delta_index` alone, since each
+/// `StreamChoice` streams its own independent set of tool calls whose
+/// indices restart at 0.
+fn accumulate_tool_call_deltas(
+    acc: &mut HashMap<(i32, i32), ToolCall>,
+    choice_index: i32,
+    deltas: Vec<ToolCall>,
+) {
+    for delta in deltas {
+        let index = delta.index.unwrap_or(0);
+        let entry = acc.entry((choice_index, index)).or_insert_with(|| ToolCall {
+            index: Some(index),
+            ..Default::default()
+        });
+        if delta.id.is_some() {
+            entry.id = delta.id;
+        }
+        if delta.type_.is_some() {
+            entry.type_ = delta.type_;
+        }
+        if delta.function.name.is_some() {
+            entry.function.name = delta.function.name;
+        }
+        if let Some(fragment) = delta.function.arguments {
+            entry
+                .function
+                .arguments
+                .get_or_insert_with(String::new)
+                .push_str(&fragment);
+        }
+    }
 }
 
 impl QwenClient {
@@ -118,7 +293,33 @@ impl QwenClient {
         Self {
             client: Client::new(),
             api_token,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Builds a client with a custom timeout, proxy, and retry policy. Use
+    /// [`QwenClient::new`] for the defaults (no timeout, no proxy, 3
+    /// attempts with exponential backoff).
+    pub fn with_config(api_token: String, config: QwenClientConfig) -> Result<Self> {
+        let mut builder = Client::builder();
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(proxy_url) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy_url).map_err(|e| ApiError::Internal {
+                message: format!("Invalid proxy URL: {}", e),
+            })?;
+            builder = builder.proxy(proxy);
         }
+        let client = builder.build().map_err(|e| ApiError::Internal {
+            message: format!("Failed to build HTTP client: {}", e),
+        })?;
+
+        Ok(Self {
+            client,
+            api_token,
+            retry: config.retry,
+        })
     }
 
     pub(crate) fn build_headers(
@@ -148,6 +349,21 @@ impl QwenClient {
         Ok(headers)
     }
 
+    /// Joins multiple system messages into the single `system` entry the
+    /// API expects, preserving their original order.
+    fn collapse_system_messages(messages: &[Message]) -> Option<String> {
+        if messages.is_empty() {
+            return None;
+        }
+        Some(
+            messages
+                .iter()
+                .map(|msg| msg.content.as_text())
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        )
+    }
+
     pub(crate) fn build_request(
         &self,
         messages: Vec<Message>,
@@ -155,18 +371,38 @@ impl QwenClient {
         stream: bool,
         config: &ApiConfig,
     ) -> QwenRequest {
-        let filtered_messages = messages
-            .into_iter()
-            .filter(|msg| msg.role != Role::System)
-            .map(|msg| QwenMessage {
-                role: match msg.role {
-                    Role::User => Some("user".to_string()),
-                    Role::Assistant => Some("assistant".to_string()),
-                    Role::System => unreachable!(),
-                },
-                content: Some(msg.content),
-            })
-            .collect();
+        let (system_messages, other_messages): (Vec<Message>, Vec<Message>) =
+            messages.into_iter().partition(|msg| msg.role == Role::System);
+
+        // A `system` field set directly on `config.body` takes precedence
+        // over system messages in the conversation.
+        let system_content = config
+            .body
+            .get("system")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .or_else(|| Self::collapse_system_messages(&system_messages));
+
+        let mut filtered_messages: Vec<QwenMessage> = Vec::with_capacity(other_messages.len() + 1);
+        if let Some(system_content) = system_content {
+            filtered_messages.push(QwenMessage {
+                role: Some("system".to_string()),
+                content: Some(MessageContent::Text(system_content)),
+                tool_calls: None,
+                tool_call_id: None,
+            });
+        }
+        filtered_messages.extend(other_messages.into_iter().map(|msg| QwenMessage {
+            role: match msg.role {
+                Role::User => Some("user".to_string()),
+                Role::Assistant => Some("assistant".to_string()),
+                Role::Tool => Some("tool".to_string()),
+                Role::System => unreachable!("system messages were partitioned out above"),
+            },
+            tool_calls: msg.tool_calls,
+            tool_call_id: msg.tool_call_id,
+            content: Some(msg.content),
+        }));
 
         let default_model = serde_json::json!(DEFAULT_MODEL);
         let model_value = config.body.get("model").unwrap_or(&default_model);
@@ -183,7 +419,8 @@ impl QwenClient {
             if let serde_json::Value::Object(mut body) =
                 serde_json::to_value(&config.body).unwrap_or_default()
             {
-                // Remove protected fields from config body
+                // Remove protected fields from config body; `system` was
+                // already folded into `messages` above.
                 body.remove("stream");
                 body.remove("messages");
                 body.remove("system");
@@ -200,6 +437,7 @@ impl QwenClient {
             messages: filtered_messages,
 
             stream,
+            tools: None,
             additional_params: config.body.clone(),
         })
     }
@@ -209,19 +447,7 @@ impl QwenClient {
         let headers = self.build_headers(Some(&config.headers))?;
         let request = self.build_request(messages, false, config);
 
-        let response = self
-            .client
-            .post(QWEN_API_URL)
-            .headers(headers)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| ApiError::QwenError {
-                message: format!("Request failed: {}", e),
-                type_: "request_failed".to_string(),
-                param: None,
-                code: None,
-            })?;
+        let response = send_with_retry(&self.client, &self.retry, &headers, &request).await?;
 
         if !response.status().is_success() {
             let error = response
@@ -233,6 +459,7 @@ impl QwenClient {
                 type_: "api_error".to_string(),
                 param: None,
                 code: None,
+                request_id: None,
             });
         }
 
@@ -244,6 +471,7 @@ impl QwenClient {
                 type_: "parse_error".to_string(),
                 param: None,
                 code: None,
+                request_id: None,
             })
     }
 
@@ -260,60 +488,309 @@ impl QwenClient {
 
         let request = self.build_request(messages, true, config);
         let client = self.client.clone();
+        let retry = self.retry.clone();
         Box::pin(async_stream::try_stream! {
-            let mut stream = client
-                .post(QWEN_API_URL)
-                .headers(headers)
-                .json(&request)
-                .send()
-                .await
-                .map_err(|e| ApiError::QwenError {
-                    message: format!("Request failed: {}", e),
-                    type_: "request_failed".to_string(),
-                    param: None,
-                    code: None
-                })?
+            let mut stream = send_with_retry(&client, &retry, &headers, &request)
+                .await?
                 .bytes_stream();
 
-            let mut data = String::new();
-
+            let mut decoder = SseDecoder::new();
+            let mut tool_call_acc: HashMap<(i32, i32), ToolCall> = HashMap::new();
 
             while let Some(chunk) = stream.next().await {
-
                 let chunk = chunk.map_err(|e| ApiError::QwenError {
                     message: format!("Stream error: {}", e),
                     type_: "stream_error".to_string(),
                     param: None,
-                    code: None
+                    code: None,
+                    request_id: None,
                 })?;
-                data.push_str(&String::from_utf8_lossy(&chunk));
-
-                let mut start = 0;
-                while let Some(end) = data[start..].find("\n\n") {
-                    let end = start + end;
-                    let line = &data[start..end];
-                    start = end + 2;
-
-                    if line.starts_with("data: ") {
-                        let mut json_data=line.to_string();
-                        if line.contains("[DONE]") {
-                            json_data="{\"data\": \"NONE\"}".to_string();
-                        }else{
-                            json_data=json_data.replace("data: {", "{\"data\": \"data\",");
+
+                for payload in decoder.push(&chunk) {
+                    if payload == "[DONE]" {
+                        yield StreamEvent::Done;
+                        continue;
+                    }
+
+                    let value: serde_json::Value = match serde_json::from_str(&payload) {
+                        Ok(v) => v,
+                        Err(e) => Err(ApiError::QwenError {
+                            message: format!("Failed to parse stream event: {}", e),
+                            type_: "stream_parse_error".to_string(),
+                            param: None,
+                            code: None,
+                            request_id: None,
+                        })?,
+                    };
+
+                    if value.get("choices").is_none() {
+                        if let Some(stream_error) = parse_stream_error(&value) {
+                            Err(ApiError::QwenError {
+                                message: stream_error.message,
+                                type_: "stream_error_event".to_string(),
+                                param: None,
+                                code: stream_error.code,
+                                request_id: stream_error.request_id,
+                            })?;
                         }
-                                if let Ok(event) = serde_json::from_str::<StreamEvent>(json_data.as_str()) {
-                                    // info!("event: {:?}", event);
-                                    yield event;
+                    }
+
+                    let mut event: StreamEvent = match serde_json::from_value::<QwenStreamChunk>(value) {
+                        Ok(chunk) => StreamEvent::Message(chunk),
+                        Err(e) => Err(ApiError::QwenError {
+                            message: format!("Failed to parse stream event: {}", e),
+                            type_: "stream_parse_error".to_string(),
+                            param: None,
+                            code: None,
+                            request_id: None,
+                        })?,
+                    };
+
+                    if let StreamEvent::Message(chunk) = &mut event {
+                        for choice in chunk.choices.iter_mut() {
+                            if let Some(deltas) = choice.delta.tool_calls.take() {
+                                accumulate_tool_call_deltas(&mut tool_call_acc, choice.index, deltas);
+                            }
+                            if choice.finish_reason.as_deref() == Some("tool_calls") {
+                                let keys: Vec<(i32, i32)> = tool_call_acc
+                                    .keys()
+                                    .filter(|(choice_index, _)| *choice_index == choice.index)
+                                    .copied()
+                                    .collect();
+                                if !keys.is_empty() {
+                                    let mut calls: Vec<ToolCall> = keys
+                                        .into_iter()
+                                        .map(|key| tool_call_acc.remove(&key).unwrap())
+                                        .collect();
+                                    calls.sort_by_key(|call| call.index.unwrap_or(0));
+                                    choice.delta.tool_calls = Some(calls);
                                 }
                             }
+                        }
+                    }
 
-
+                    yield event;
                 }
+            }
+        })
+    }
+
+    /// Drives [`chat_stream`](Self::chat_stream) to completion and
+    /// reassembles it into a single [`QwenResponse`], for callers who want
+    /// streaming transport but don't need incremental output: content
+    /// deltas are concatenated per choice index, and the final `usage` and
+    /// `finish_reason` (only present on the last chunk) are captured.
+    pub async fn chat_stream_collect(
+        &self,
+        messages: Vec<Message>,
+        config: &ApiConfig,
+    ) -> Result<QwenResponse> {
+        let mut stream = self.chat_stream(messages, config);
+
+        let mut id = String::new();
+        let mut created = 0i64;
+        let mut model = String::new();
+        let mut usage: Option<Usage> = None;
+        let mut contents: HashMap<i32, String> = HashMap::new();
+        let mut tool_calls: HashMap<i32, Vec<ToolCall>> = HashMap::new();
+        let mut finish_reasons: HashMap<i32, String> = HashMap::new();
+
+        while let Some(event) = stream.next().await {
+            let chunk = match event? {
+                StreamEvent::Message(chunk) => chunk,
+                StreamEvent::Done => break,
+            };
+
+            id = chunk.id;
+            created = chunk.created;
+            model = chunk.model;
+            if let Some(chunk_usage) = chunk.usage {
+                usage = Some(chunk_usage);
+            }
 
-                if start > 0 {
-                    data = data[start..].to_string();
+            for choice in chunk.choices {
+                if let Some(content) = &choice.delta.content {
+                    contents.entry(choice.index).or_default().push_str(&content.as_text());
+                }
+                if let Some(calls) = choice.delta.tool_calls {
+                    tool_calls.entry(choice.index).or_default().extend(calls);
+                }
+                if let Some(finish_reason) = choice.finish_reason {
+                    finish_reasons.insert(choice.index, finish_reason);
                 }
             }
+        }
+
+        let mut indices: Vec<i32> = contents
+            .keys()
+            .chain(finish_reasons.keys())
+            .chain(tool_calls.keys())
+            .copied()
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let choices = indices
+            .into_iter()
+            .map(|index| Choice {
+                index,
+                message: Message {
+                    role: Role::Assistant,
+                    content: MessageContent::Text(contents.remove(&index).unwrap_or_default()),
+                    tool_call_id: None,
+                    tool_calls: tool_calls.remove(&index),
+                },
+                finish_reason: finish_reasons.remove(&index),
+            })
+            .collect();
+
+        Ok(QwenResponse {
+            id,
+            object: "chat.completion".to_string(),
+            created,
+            model,
+            choices,
+            usage: usage.unwrap_or(Usage {
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                total_tokens: 0,
+            }),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_call_delta(index: i32, id: Option<&str>, name: Option<&str>, args: &str) -> ToolCall {
+        ToolCall {
+            index: Some(index),
+            id: id.map(str::to_string),
+            type_: id.map(|_| "function".to_string()),
+            function: crate::models::ToolCallFunction {
+                name: name.map(str::to_string),
+                arguments: Some(args.to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn accumulate_tool_call_deltas_concatenates_argument_fragments() {
+        let mut acc = HashMap::new();
+        accumulate_tool_call_deltas(
+            &mut acc,
+            0,
+            vec![tool_call_delta(0, Some("call_1"), Some("get_weather"), "{\"loc")],
+        );
+        accumulate_tool_call_deltas(&mut acc, 0, vec![tool_call_delta(0, None, None, "ation\":\"sf\"}")]);
+
+        let call = acc.get(&(0, 0)).expect("tool call at (choice 0, index 0)");
+        assert_eq!(call.id.as_deref(), Some("call_1"));
+        assert_eq!(call.function.name.as_deref(), Some("get_weather"));
+        assert_eq!(call.function.arguments.as_deref(), Some("{\"location\":\"sf\"}"));
+    }
+
+    #[test]
+    fn accumulate_tool_call_deltas_keeps_choices_independent() {
+        let mut acc = HashMap::new();
+        accumulate_tool_call_deltas(&mut acc, 0, vec![tool_call_delta(0, Some("call_a"), Some("fn_a"), "1")]);
+        accumulate_tool_call_deltas(&mut acc, 1, vec![tool_call_delta(0, Some("call_b"), Some("fn_b"), "2")]);
+
+        assert_eq!(acc.len(), 2);
+        assert_eq!(acc[&(0, 0)].id.as_deref(), Some("call_a"));
+        assert_eq!(acc[&(1, 0)].id.as_deref(), Some("call_b"));
+    }
+
+    #[test]
+    fn parse_stream_error_reads_nested_error_object() {
+        let value = serde_json::json!({
+            "error": {"code": "rate_limit_exceeded", "message": "too many requests"},
+            "request_id": "req-123",
+        });
+        let err = parse_stream_error(&value).expect("should parse nested error");
+        assert_eq!(err.code.as_deref(), Some("rate_limit_exceeded"));
+        assert_eq!(err.message, "too many requests");
+        assert_eq!(err.request_id.as_deref(), Some("req-123"));
+    }
+
+    #[test]
+    fn parse_stream_error_reads_top_level_fields() {
+        let value = serde_json::json!({"code": 400, "message": "bad request"});
+        let err = parse_stream_error(&value).expect("should parse top-level error");
+        assert_eq!(err.code.as_deref(), Some("400"));
+        assert_eq!(err.message, "bad request");
+        assert_eq!(err.request_id, None);
+    }
+
+    #[test]
+    fn parse_stream_error_returns_none_without_a_message() {
+        let value = serde_json::json!({"choices": []});
+        assert!(parse_stream_error(&value).is_none());
+    }
+
+    #[test]
+    fn next_backoff_doubles_until_the_cap() {
+        let max = Duration::from_secs(8);
+        assert_eq!(next_backoff(Duration::from_millis(500), max), Duration::from_secs(1));
+        assert_eq!(next_backoff(Duration::from_secs(6), max), max);
+        assert_eq!(next_backoff(max, max), max);
+    }
+
+    #[test]
+    fn parse_retry_after_accepts_seconds_and_rejects_garbage() {
+        assert_eq!(parse_retry_after("5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_retry_after("not-a-number"), None);
+    }
+
+    #[test]
+    fn collapse_system_messages_joins_in_order() {
+        let messages = vec![
+            Message {
+                role: Role::System,
+                content: MessageContent::Text("be terse".to_string()),
+                tool_call_id: None,
+                tool_calls: None,
+            },
+            Message {
+                role: Role::System,
+                content: MessageContent::Text("avoid markdown".to_string()),
+                tool_call_id: None,
+                tool_calls: None,
+            },
+        ];
+        let collapsed = QwenClient::collapse_system_messages(&messages);
+        assert_eq!(collapsed.as_deref(), Some("be terse\n\navoid markdown"));
+    }
+
+    #[test]
+    fn collapse_system_messages_returns_none_for_empty_input() {
+        assert_eq!(QwenClient::collapse_system_messages(&[]), None);
+    }
+
+    #[test]
+    fn build_request_moves_system_messages_to_the_front() {
+        let client = QwenClient::new("token".to_string());
+        let messages = vec![
+            Message {
+                role: Role::User,
+                content: MessageContent::Text("hi".to_string()),
+                tool_call_id: None,
+                tool_calls: None,
+            },
+            Message {
+                role: Role::System,
+                content: MessageContent::Text("be terse".to_string()),
+                tool_call_id: None,
+                tool_calls: None,
+            },
+        ];
+        let config = ApiConfig::default();
+        let request = client.build_request(messages, false, &config);
+
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role.as_deref(), Some("system"));
+        assert_eq!(request.messages[0].content.as_ref().unwrap().as_text(), "be terse");
+        assert_eq!(request.messages[1].role.as_deref(), Some("user"));
+    }
+}
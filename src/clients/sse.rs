@@ -0,0 +1,152 @@
+//! A minimal line-oriented Server-Sent Events decoder.
+//!
+//! Buffers raw bytes from an HTTP response body and yields complete event
+//! payloads (the concatenated `data:` lines of each event) as soon as a
+//! full event has arrived, regardless of how the transport chunked the
+//! underlying bytes (multi-line `data:` frames, CRLF line endings, partial
+//! reads split mid-event).
+
+/// Incrementally decodes an SSE byte stream into event payloads.
+#[derive(Debug, Default)]
+pub(crate) struct SseDecoder {
+    buffer: String,
+    /// Bytes received so far that don't yet form a complete UTF-8 sequence,
+    /// e.g. the first half of a multi-byte character split across two
+    /// `bytes_stream()` reads. Held back until more bytes complete them, so
+    /// a chunk boundary never lossy-decodes a character in isolation.
+    pending_bytes: Vec<u8>,
+}
+
+impl SseDecoder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds newly received bytes into the decoder and returns the
+    /// payloads of any events that became complete as a result, in order.
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending_bytes.extend_from_slice(chunk);
+
+        loop {
+            match std::str::from_utf8(&self.pending_bytes) {
+                Ok(s) => {
+                    self.buffer.push_str(s);
+                    self.pending_bytes.clear();
+                    break;
+                }
+                Err(e) => {
+                    let valid_len = e.valid_up_to();
+                    let valid = std::str::from_utf8(&self.pending_bytes[..valid_len])
+                        .expect("validated by valid_up_to");
+                    self.buffer.push_str(valid);
+                    match e.error_len() {
+                        // Genuinely invalid bytes (not just a split
+                        // character): replace them with U+FFFD and keep
+                        // scanning.
+                        Some(bad_len) => {
+                            self.buffer.push('\u{FFFD}');
+                            self.pending_bytes.drain(..valid_len + bad_len);
+                        }
+                        // An incomplete sequence at the end of the buffer;
+                        // wait for the bytes that complete it.
+                        None => {
+                            self.pending_bytes.drain(..valid_len);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.buffer.contains('\r') {
+            self.buffer = self.buffer.replace("\r\n", "\n");
+        }
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.find("\n\n") {
+            let raw_event = self.buffer[..end].to_string();
+            self.buffer.drain(..end + 2);
+
+            if let Some(payload) = Self::extract_payload(&raw_event) {
+                events.push(payload);
+            }
+        }
+        events
+    }
+
+    /// Concatenates the `data:` lines of a raw event block into a single
+    /// payload, per the SSE spec. `event:`, `id:`, `retry:` fields and
+    /// comment lines (starting with `:`) are recognized and ignored.
+    fn extract_payload(raw_event: &str) -> Option<String> {
+        let mut data_lines = Vec::new();
+        for line in raw_event.split('\n') {
+            if let Some(rest) = line.strip_prefix("data:") {
+                data_lines.push(rest.strip_prefix(' ').unwrap_or(rest));
+            }
+        }
+        if data_lines.is_empty() {
+            None
+        } else {
+            Some(data_lines.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_event_in_one_push() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b"data: {\"a\":1}\n\n");
+        assert_eq!(events, vec!["{\"a\":1}".to_string()]);
+    }
+
+    #[test]
+    fn event_split_across_two_pushes() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b"data: {\"a\"").is_empty());
+        let events = decoder.push(b":1}\n\n");
+        assert_eq!(events, vec!["{\"a\":1}".to_string()]);
+    }
+
+    #[test]
+    fn multi_line_data_is_joined_with_newlines() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b"data: line one\ndata: line two\n\n");
+        assert_eq!(events, vec!["line one\nline two".to_string()]);
+    }
+
+    #[test]
+    fn crlf_line_endings_are_normalized() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b"data: hello\r\n\r\n");
+        assert_eq!(events, vec!["hello".to_string()]);
+    }
+
+    #[test]
+    fn event_and_id_fields_are_ignored() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b"event: message\nid: 42\ndata: hi\n\n");
+        assert_eq!(events, vec!["hi".to_string()]);
+    }
+
+    #[test]
+    fn done_sentinel_round_trips_as_a_payload() {
+        let mut decoder = SseDecoder::new();
+        let events = decoder.push(b"data: [DONE]\n\n");
+        assert_eq!(events, vec!["[DONE]".to_string()]);
+    }
+
+    #[test]
+    fn multi_byte_character_split_across_pushes_is_not_corrupted() {
+        let mut decoder = SseDecoder::new();
+        let frame = "data: café\n\n".as_bytes().to_vec();
+        // Split inside the two-byte UTF-8 encoding of 'é'.
+        let split_at = frame.len() - 3;
+        assert!(decoder.push(&frame[..split_at]).is_empty());
+        let events = decoder.push(&frame[split_at..]);
+        assert_eq!(events, vec!["café".to_string()]);
+    }
+}
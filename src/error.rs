@@ -0,0 +1,26 @@
+//! Error types shared across provider clients.
+
+use thiserror::Error;
+
+/// Convenience alias for results that can fail with an [`ApiError`].
+pub type Result<T> = std::result::Result<T, ApiError>;
+
+/// Errors that can occur while talking to a provider's API.
+#[derive(Debug, Error)]
+pub enum ApiError {
+    /// A local failure unrelated to the remote API (e.g. an invalid header).
+    #[error("{message}")]
+    Internal { message: String },
+
+    /// An error returned by (or while talking to) the Qwen/DashScope API.
+    #[error("{message}")]
+    QwenError {
+        message: String,
+        type_: String,
+        param: Option<String>,
+        code: Option<String>,
+        /// DashScope's `request_id` for the failed call, when available;
+        /// useful when reporting issues to Alibaba support.
+        request_id: Option<String>,
+    },
+}
@@ -0,0 +1,5 @@
+//! Provider clients for chat-completion style APIs (currently Qwen).
+
+pub mod clients;
+pub mod error;
+pub mod models;
@@ -0,0 +1,124 @@
+//! Shared request/response types used across provider clients.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Conversational role of a [`Message`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    /// A tool result being fed back to the model; pairs with
+    /// [`Message::tool_call_id`].
+    Tool,
+}
+
+/// A single chat message exchanged with a model.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: MessageContent,
+
+    /// For `Role::Tool` messages, the id of the [`ToolCall`] this message
+    /// answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+
+    /// Tool calls requested by the model, present on assistant messages
+    /// when `finish_reason == "tool_calls"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+/// A model-requested invocation of a tool/function.
+///
+/// When streamed, a given `index` may arrive across several deltas: `id`,
+/// `type_` and `function.name` are only present on the first one, while
+/// `function.arguments` is split into fragments that must be concatenated
+/// in order until `finish_reason == "tool_calls"`.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    #[serde(default)]
+    pub function: ToolCallFunction,
+}
+
+/// The function half of a [`ToolCall`].
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct ToolCallFunction {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// JSON-encoded arguments, as returned by the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
+}
+
+/// The content of a [`Message`]: either plain text, or a sequence of typed
+/// parts used for multimodal prompts (text interleaved with images).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    /// Flattens the content down to plain text, joining text parts with
+    /// newlines and dropping non-text parts (e.g. images).
+    pub fn as_text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        MessageContent::Text(text.to_string())
+    }
+}
+
+/// A single part of a multimodal [`MessageContent`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+/// An image reference within a [`ContentPart`]. `url` may be a remote
+/// `http(s)://` URL or a base64-encoded `data:` URI.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+/// Per-request configuration shared by every provider client: custom
+/// headers plus a freeform JSON body merged into the provider's request
+/// payload (model name, sampling parameters, tool definitions, etc.).
+#[derive(Debug, Clone, Default)]
+pub struct ApiConfig {
+    pub headers: HashMap<String, String>,
+    pub body: serde_json::Value,
+}